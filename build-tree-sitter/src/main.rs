@@ -3,9 +3,11 @@ use clap::Parser;
 use dunce::canonicalize;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashSet,
     fs::{self},
     path::{Path, PathBuf},
     process::Command,
+    sync::mpsc,
     time::SystemTime,
 };
 use tracing::{debug, error, info, Level};
@@ -18,25 +20,103 @@ struct Cli {
     dir: Option<PathBuf>,
     #[clap(short, long)]
     output: PathBuf,
+    /// Number of grammars to build concurrently, defaults to the number of available cores.
+    #[clap(short, long)]
+    jobs: Option<usize>,
+    /// Only build these grammars, overriding `use-grammars` in the config.
+    #[clap(long, value_delimiter = ',')]
+    only: Vec<String>,
+    /// Build every grammar except these, overriding `use-grammars` in the config.
+    #[clap(long, value_delimiter = ',')]
+    except: Vec<String>,
+    /// Output format for the built grammars.
+    #[clap(long, value_enum, default_value_t = BuildTarget::Native)]
+    target: BuildTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum BuildTarget {
+    /// Native shared object, loadable with `libloading`/`dlopen`.
+    Native,
+    /// `.wasm` module, loadable with `web-tree-sitter`.
+    Wasm,
+    /// Native static archive, for linking into a Rust crate at build time.
+    Static,
 }
 
 #[non_exhaustive]
 #[derive(Debug, Deserialize, Serialize)]
 struct GrammarsFile {
     grammars: std::collections::HashMap<String, GrammarBuildInfo>,
+    #[serde(rename = "use-grammars")]
+    use_grammars: Option<GrammarSelection>,
 }
 
-#[non_exhaustive]
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum GrammarSelection {
+    #[serde(deny_unknown_fields)]
+    Only {
+        #[serde(default)]
+        only: HashSet<String>,
+    },
+    #[serde(deny_unknown_fields)]
+    Except {
+        #[serde(default)]
+        except: HashSet<String>,
+    },
+}
+
+impl GrammarSelection {
+    fn filter(&self, grammars: &mut std::collections::HashMap<String, GrammarBuildInfo>) {
+        match self {
+            GrammarSelection::Only { only } if !only.is_empty() => {
+                grammars.retain(|name, _| only.contains(name))
+            }
+            GrammarSelection::Except { except } if !except.is_empty() => {
+                grammars.retain(|name, _| !except.contains(name))
+            }
+            // An empty `only`/`except` set (or no selection given at all) means "all".
+            _ => {}
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct GrammarBuildInfo {
-    git: String,
-    rev: Option<String>,
-    path: PathBuf,
+    #[serde(flatten)]
+    source: GrammarSource,
     cpp: Option<bool>,
     relative: Option<PathBuf>,
     generate: Option<bool>,
 }
 
+/// Where to find a grammar's sources: either cloned from `git`, or already
+/// checked out at `path` on disk.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum GrammarSource {
+    Git {
+        git: String,
+        rev: Option<String>,
+        path: PathBuf,
+        submodules: Option<bool>,
+    },
+    Local {
+        path: PathBuf,
+    },
+}
+
+impl GrammarSource {
+    fn path(&self) -> &Path {
+        match self {
+            GrammarSource::Git { path, .. } => path,
+            GrammarSource::Local { path } => path,
+        }
+    }
+}
+
 fn logging() -> Result<()> {
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::DEBUG)
@@ -73,26 +153,102 @@ fn main() -> Result<()> {
         error!("Failed to read grammars config");
         bail!("Failed to read grammars config");
     };
-    let Ok(config) = toml::from_str::<GrammarsFile>(grammars) else {
+    let Ok(mut config) = toml::from_str::<GrammarsFile>(grammars) else {
         error!("Failed to deserialize config");
         bail!("Failed to deserialize config");
     };
 
-    for (name, grammar) in config.grammars {
-        info!("Building: {name}");
+    let cli_selection = if !cli.only.is_empty() {
+        Some(GrammarSelection::Only {
+            only: cli.only.into_iter().collect(),
+        })
+    } else if !cli.except.is_empty() {
+        Some(GrammarSelection::Except {
+            except: cli.except.into_iter().collect(),
+        })
+    } else {
+        None
+    };
+
+    if let Some(selection) = cli_selection.as_ref().or(config.use_grammars.as_ref()) {
+        selection.filter(&mut config.grammars);
+    }
+
+    run_parallel(config.grammars, &output_dir, cli.jobs, cli.target)
+}
+
+fn run_parallel(
+    grammars: std::collections::HashMap<String, GrammarBuildInfo>,
+    output_dir: &Path,
+    jobs: Option<usize>,
+    target: BuildTarget,
+) -> Result<()> {
+    let n_jobs = grammars.len();
+
+    let mut builder = threadpool::Builder::new();
+    if let Some(jobs) = jobs {
+        if jobs == 0 {
+            bail!("--jobs must be at least 1");
+        }
+        builder = builder.num_threads(jobs);
+    }
+    let pool = builder.build();
+
+    let (tx, rx) = mpsc::channel();
+
+    for (name, grammar) in grammars {
+        let tx = tx.clone();
+        let output_dir = output_dir.to_path_buf();
+        pool.execute(move || {
+            let ok = match build_grammar(&name, &grammar, &output_dir, target) {
+                Ok(()) => true,
+                Err(e) => {
+                    println!("Failed to build grammar '{name}': {e}");
+                    false
+                }
+            };
+            tx.send(ok).expect("failed to report job result");
+        });
+    }
+    drop(tx);
+
+    pool.join();
 
-        if grammar.path.exists() {
+    let successes = rx.into_iter().filter(|ok| *ok).count();
+    if successes != n_jobs {
+        bail!("{} of {n_jobs} grammars failed to build", n_jobs - successes);
+    }
+
+    Ok(())
+}
+
+fn build_grammar(
+    name: &str,
+    grammar: &GrammarBuildInfo,
+    output_dir: &Path,
+    target: BuildTarget,
+) -> Result<()> {
+    info!("Building: {name}");
+
+    if let GrammarSource::Git {
+        git,
+        rev,
+        path,
+        submodules,
+    } = &grammar.source
+    {
+        if path.exists() {
             let output = Command::new("git")
-                .current_dir(&grammar.path)
+                .current_dir(path)
                 .arg("fetch")
                 .output()?;
             if !output.status.success() {
                 return Err(anyhow!("git fetch failed"));
             }
 
-            if let Some(rev) = &grammar.rev {
+            if let Some(rev) = rev {
                 let output = Command::new("git")
-                    .current_dir(&grammar.path)
+                    .current_dir(path)
                     .arg("checkout")
                     .arg(rev)
                     .output()?;
@@ -101,20 +257,20 @@ fn main() -> Result<()> {
                 }
             }
         } else {
-            std::fs::create_dir_all(&grammar.path)?;
+            std::fs::create_dir_all(path)?;
             let output = Command::new("git")
-                .current_dir(&grammar.path)
+                .current_dir(path)
                 .arg("clone")
-                .arg(&grammar.git)
+                .arg(git)
                 .arg(".")
                 .output()?;
             if !output.status.success() {
                 return Err(anyhow!("git clone failed"));
             }
 
-            if let Some(rev) = &grammar.rev {
+            if let Some(rev) = rev {
                 let output = Command::new("git")
-                    .current_dir(&grammar.path)
+                    .current_dir(path)
                     .arg("checkout")
                     .arg(rev)
                     .output()?;
@@ -124,26 +280,177 @@ fn main() -> Result<()> {
             }
         }
 
-        let grammar_path = match canonicalize(&grammar.path) {
-            Ok(v) => v,
-            Err(e) => {
-                error!("Failed to canonicalize '{}': {e}", grammar.path.display());
-                continue;
+        if submodules.unwrap_or(false) {
+            let output = Command::new("git")
+                .current_dir(path)
+                .arg("submodule")
+                .arg("update")
+                .arg("--init")
+                .arg("--recursive")
+                .output()?;
+            if !output.status.success() {
+                return Err(anyhow!("git submodule update failed"));
             }
-        };
-        let paths = TreeSitterPaths::new(
-            grammar_path,
-            grammar.relative,
-            grammar.cpp,
-            grammar.generate,
+        }
+    }
+
+    let grammar_path = canonicalize(grammar.source.path())
+        .with_context(|| format!("Failed to canonicalize '{}'", grammar.source.path().display()))?;
+    let paths = TreeSitterPaths::new(
+        grammar_path,
+        grammar.relative.clone(),
+        grammar.cpp,
+        grammar.generate,
+    );
+    match target {
+        BuildTarget::Native => {
+            build_tree_sitter_library(&paths, output_dir, name)?;
+        }
+        BuildTarget::Wasm => {
+            build_tree_sitter_wasm(&paths, output_dir, name)?;
+        }
+        BuildTarget::Static => {
+            build_tree_sitter_static(&paths, output_dir, name)?;
+        }
+    }
+    Ok(())
+}
+
+fn build_tree_sitter_wasm(paths: &TreeSitterPaths, output: &Path, name: &str) -> Result<bool> {
+    let library_path = output.join(name).with_extension("wasm");
+    info!("Build object: {}", library_path.display());
+
+    let should_recompile = paths.should_recompile(&library_path)?;
+    if !should_recompile {
+        return Ok(false);
+    }
+
+    let grammar_root = paths
+        .source
+        .parent()
+        .ok_or_else(|| anyhow!("grammar source directory has no parent"))?;
+
+    let mut command = Command::new("tree-sitter");
+    command
+        .current_dir(grammar_root)
+        .arg("build")
+        .arg("--wasm")
+        .arg("--output")
+        .arg(&library_path);
+
+    let command_str = format!("{command:?}");
+    debug!("Running {command_str}");
+    let output_result = command
+        .output()
+        .with_context(|| format!("Failed to run tree-sitter. Command: {command_str}"))?;
+    if !output_result.status.success() {
+        bail!(
+            "wasm build failed for {name}:\nCommand: {command_str}\nStdout: {}\nStderr: {}",
+            String::from_utf8_lossy(&output_result.stdout),
+            String::from_utf8_lossy(&output_result.stderr)
+        );
+    }
+
+    Ok(true)
+}
+
+/// Compile `paths` into object files and archive them into `lib{name}.a`, for
+/// downstream crates that want to link the grammar statically instead of
+/// `dlopen`-ing a shared object at runtime.
+fn build_tree_sitter_static(paths: &TreeSitterPaths, output: &Path, name: &str) -> Result<bool> {
+    let library_path = output.join(format!("lib{name}.a"));
+    info!("Build object: {}", library_path.display());
+
+    let should_recompile = paths.should_recompile(&library_path)?;
+    if !should_recompile {
+        return Ok(false);
+    }
+
+    let cpp = if let Some(TreeSitterScannerSource { path: _, cpp }) = paths.scanner {
+        cpp
+    } else {
+        false
+    };
+
+    let mut compiler = cc::Build::new();
+    compiler
+        .cpp(cpp)
+        .warnings(false)
+        .include(&paths.source)
+        .opt_level(3)
+        .cargo_metadata(false)
+        .host(BUILD_TARGET)
+        .target(BUILD_TARGET);
+
+    let object_dir = output.join(format!("{name}-objs"));
+    fs::create_dir_all(&object_dir)?;
+
+    let mut object_paths = vec![object_dir.join("parser.o")];
+    compile_object(&compiler, &paths.parser, &object_paths[0], false)?;
+
+    if let Some(TreeSitterScannerSource { ref path, cpp }) = paths.scanner {
+        let scanner_object = object_dir.join("scanner.o");
+        compile_object(&compiler, path, &scanner_object, cpp)?;
+        object_paths.push(scanner_object);
+    }
+
+    let mut archiver = Command::new(if cfg!(windows) { "lib" } else { "ar" });
+    if cfg!(windows) {
+        archiver
+            .arg(format!("/OUT:{}", library_path.display()))
+            .args(&object_paths);
+    } else {
+        archiver.arg("crs").arg(&library_path).args(&object_paths);
+    }
+
+    let command_str = format!("{archiver:?}");
+    debug!("Running {command_str}");
+    let archive_output = archiver
+        .output()
+        .with_context(|| format!("Failed to run archiver. Command: {command_str}"))?;
+    if !archive_output.status.success() {
+        bail!(
+            "static archive creation failed for {name}:\nCommand: {command_str}\nStdout: {}\nStderr: {}",
+            String::from_utf8_lossy(&archive_output.stdout),
+            String::from_utf8_lossy(&archive_output.stderr)
+        );
+    }
+
+    Ok(true)
+}
+
+/// Compile a single source file to an object file with `compiler`, used to
+/// build up the inputs for [`build_tree_sitter_static`]'s archive step.
+fn compile_object(compiler: &cc::Build, source: &Path, object_path: &Path, cpp: bool) -> Result<()> {
+    let mut command = compiler.try_get_compiler()?.to_command();
+    if cfg!(windows) {
+        command.arg("/c").arg(source);
+        if cpp {
+            command.arg("/TP");
+        }
+        command.arg(format!("/Fo{}", object_path.display()));
+    } else {
+        command.arg("-fPIC").arg("-fno-exceptions").arg("-g");
+        if cpp {
+            command.arg("-xc++");
+        } else {
+            command.arg("-xc").arg("-std=c99");
+        }
+        command.arg(source).arg("-c").arg("-o").arg(object_path);
+    }
+
+    let command_str = format!("{command:?}");
+    debug!("Running {command_str}");
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run C compiler. Command: {command_str}"))?;
+    if !output.status.success() {
+        bail!(
+            "object compilation failed for '{}':\nCommand: {command_str}\nStdout: {}\nStderr: {}",
+            source.display(),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
         );
-        match build_tree_sitter_library(&paths, &output_dir, &name) {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Failed to build grammar: {e}");
-                continue;
-            }
-        };
     }
 
     Ok(())
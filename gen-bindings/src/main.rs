@@ -0,0 +1,113 @@
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use serde::{Deserialize, Serialize};
+use tracing::{error, Level};
+use tracing_subscriber::FmtSubscriber;
+
+#[derive(Parser)]
+struct Cli {
+    /// Directory holding the `lib{name}.a` archives from `build-tree-sitter --target static`.
+    #[clap(short, long)]
+    lib_dir: PathBuf,
+    /// Where to write the generated Rust source file.
+    #[clap(short, long)]
+    output: PathBuf,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Deserialize, Serialize)]
+struct GrammarsFile {
+    grammars: std::collections::HashMap<String, toml::Value>,
+    /// Grammars left out of the generated registry (e.g. don't compile on this platform).
+    blacklist: Option<HashSet<String>>,
+}
+
+fn logging() -> Result<()> {
+    let subscriber = FmtSubscriber::builder()
+        .with_max_level(Level::DEBUG)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
+}
+
+const GRAMMARS_CONFIG: &str = "config.toml";
+
+fn main() -> Result<()> {
+    logging()?;
+
+    let cli = Cli::parse();
+
+    let Ok(grammars) = fs::read_to_string(GRAMMARS_CONFIG) else {
+        error!("Failed to read grammars config");
+        bail!("Failed to read grammars config");
+    };
+    let Ok(config) = toml::from_str::<GrammarsFile>(&grammars) else {
+        error!("Failed to deserialize config");
+        bail!("Failed to deserialize config");
+    };
+
+    let blacklist = config.blacklist.unwrap_or_default();
+    let mut names: Vec<String> = config
+        .grammars
+        .into_keys()
+        .filter(|name| !blacklist.contains(name))
+        .collect();
+    names.sort_unstable();
+
+    let bindings = generate_bindings(&names);
+    fs::write(&cli.output, bindings.to_string())?;
+
+    println!("cargo::rustc-link-search=native={}", cli.lib_dir.display());
+    for name in &names {
+        println!("cargo::rustc-link-lib=static={name}");
+    }
+
+    Ok(())
+}
+
+fn generate_bindings(names: &[String]) -> TokenStream {
+    let externs = names.iter().map(|name| {
+        let symbol = format_ident!("tree_sitter_{}", name.to_snake_case());
+        quote! {
+            extern "C" {
+                fn #symbol() -> tree_sitter::Language;
+            }
+        }
+    });
+
+    let wrappers = names.iter().map(|name| {
+        let symbol = format_ident!("tree_sitter_{}", name.to_snake_case());
+        let wrapper = format_ident!("language_{}", name.to_snake_case());
+        quote! {
+            pub fn #wrapper() -> tree_sitter::Language {
+                unsafe { #symbol() }
+            }
+        }
+    });
+
+    let dispatch_arms = names.iter().map(|name| {
+        let wrapper = format_ident!("language_{}", name.to_snake_case());
+        quote! {
+            #name => Some(#wrapper()),
+        }
+    });
+
+    quote! {
+        #(#externs)*
+
+        #(#wrappers)*
+
+        /// Look up a statically linked grammar by name.
+        pub fn language(name: &str) -> Option<tree_sitter::Language> {
+            match name {
+                #(#dispatch_arms)*
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use tree_sitter::{Language, LANGUAGE_VERSION, MIN_COMPATIBLE_LANGUAGE_VERSION};
+
+/// Load the grammar `name` from the shared library `{name}.{DLL_EXTENSION}` in
+/// `output_dir`, the file that `build-tree-sitter` writes for its native target.
+/// The `libtree-sitter-{name}` libraries written by the helix-driven builder,
+/// and the wasm and static outputs, are not found by this function.
+pub fn get_language(output_dir: &Path, name: &str) -> Result<Language> {
+    let mut library_path = output_dir.join(name);
+    library_path.set_extension(std::env::consts::DLL_EXTENSION);
+
+    let library = unsafe { libloading::Library::new(&library_path) }?;
+
+    let language = unsafe {
+        let symbol_name = format!("tree_sitter_{}", name.replace('-', "_"));
+        let constructor: libloading::Symbol<unsafe extern "C" fn() -> Language> =
+            library.get(symbol_name.as_bytes())?;
+        constructor()
+    };
+
+    // The `Language` above borrows code from `library`; keep the mapping
+    // alive for the rest of the process instead of unloading it here.
+    std::mem::forget(library);
+
+    let version = language.version();
+    if !(MIN_COMPATIBLE_LANGUAGE_VERSION..=LANGUAGE_VERSION).contains(&version) {
+        bail!(
+            "grammar '{name}' has incompatible ABI version {version} (supported range is \
+             {MIN_COMPATIBLE_LANGUAGE_VERSION}..={LANGUAGE_VERSION}), rebuild needed"
+        );
+    }
+
+    Ok(language)
+}
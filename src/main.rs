@@ -31,6 +31,7 @@ struct GrammarSource {
     git: String,
     rev: String,
     subpath: Option<String>,
+    submodules: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -53,6 +54,7 @@ struct GrammarBuildInfo {
     cpp: Option<bool>,
     relative: Option<PathBuf>,
     generate: Option<bool>,
+    submodules: Option<bool>,
 }
 
 fn logging() -> Result<()> {
@@ -93,6 +95,7 @@ fn main() -> Result<()> {
         &helix_dir,
         "https://github.com/helix-editor/helix",
         "0a4432b104099534f7a25b8ea4148234db146ab6",
+        false,
     )?;
 
     let Ok(languages_config) = &fs::read_to_string(helix_dir.join("languages.toml"))
@@ -113,7 +116,7 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn checkout_repo(path: &Path, repo: &str, rev: &str) -> Result<()> {
+fn checkout_repo(path: &Path, repo: &str, rev: &str, submodules: bool) -> Result<()> {
     if path.join(".git").exists() {
         let output = Command::new("git")
             .current_dir(path)
@@ -147,6 +150,19 @@ fn checkout_repo(path: &Path, repo: &str, rev: &str) -> Result<()> {
             .output();
     }
 
+    if submodules {
+        let output = Command::new("git")
+            .current_dir(path)
+            .arg("submodule")
+            .arg("update")
+            .arg("--init")
+            .arg("--recursive")
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("git submodule update failed for {repo}"));
+        }
+    }
+
     Ok(())
 }
 
@@ -156,7 +172,12 @@ fn build_grammar(
     tmp_dir: &Path,
 ) -> Result<()> {
     let path = tmp_dir.join(format! {"tree-sitter-{}",grammar.name});
-    checkout_repo(&path, &grammar.source.git, &grammar.source.rev)?;
+    checkout_repo(
+        &path,
+        &grammar.source.git,
+        &grammar.source.rev,
+        grammar.source.submodules.unwrap_or(false),
+    )?;
     let path = if let Some(subpath) = grammar.source.subpath.as_ref() {
         path.join(subpath)
     } else {